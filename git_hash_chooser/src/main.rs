@@ -1,11 +1,15 @@
 use clap::Parser;
 use kdam::{tqdm, BarExt};
 use rayon::prelude::*;
-use sha1::Digest;
 use std::error::Error;
-use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
 
+mod padding;
+mod repo;
+mod rewrite;
+mod signing;
+
 struct CommitValues {
     author_date_timestamp: i64,
     author_date_tz: String,
@@ -13,25 +17,41 @@ struct CommitValues {
     committer_date_tz: String,
 }
 
-fn subprocess_check_output(cmd: &str) -> Result<String, Box<dyn Error>> {
-    let output = Command::new("sh").arg("-c").arg(cmd).output()?;
-    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
-    if output.status.success() {
-        Ok(stdout)
-    } else {
-        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
-        Err(format!("Command failed: {}\n{}", cmd, stderr).into())
-    }
+/// Hashes `commit` (the object content, without the `commit <len>\0` header)
+/// the same way git would, via gitoxide's own object hashing rather than a
+/// hand-built header string. `hash_kind` is the repository's object format
+/// (`sha1`, or `sha256` for repositories created with
+/// `--object-format=sha256`).
+fn git_commit_hash(commit: &str, hash_kind: gix_hash::Kind) -> String {
+    gix_object::compute_hash(hash_kind, gix_object::Kind::Commit, commit.as_bytes())
+        .expect("hashing a commit object cannot fail")
+        .to_string()
 }
 
-fn load_git_commit(commit_id: &str) -> Result<String, Box<dyn Error>> {
-    subprocess_check_output(&format!("git cat-file commit {}", commit_id))
+/// Size of the hash space a `prefix.len()`-hex-digit prefix carves out of.
+/// Prefixes up to 64 hex digits (the sha256 width) are valid input, and
+/// `16u64.pow` overflows and panics past 15 digits, so this is computed in
+/// `f64` instead.
+fn hash_space_size(prefix_len: usize) -> f64 {
+    16f64.powi(prefix_len as i32)
 }
 
-fn git_commit_hash(commit: &str) -> String {
-    let object = format!("commit {}\x00{}", commit.len(), commit);
-    let sha = sha1::Sha1::digest(object.as_bytes());
-    format!("{:x}", sha)
+/// Checks that `prefix` is only lower-case hex digits and fits within
+/// `hash_kind`'s width (40 hex digits for sha1, 64 for sha256).
+fn validate_prefix(prefix: &str, hash_kind: gix_hash::Kind) -> Result<(), Box<dyn Error>> {
+    let allowed_prefix_chars = "0123456789abcdef";
+    if !prefix.chars().all(|c| allowed_prefix_chars.contains(c)) {
+        return Err("Invalid prefix! Only lower case hex digits are allowed".into());
+    }
+    let max_len = hash_kind.len_in_hex();
+    if prefix.len() > max_len {
+        return Err(format!(
+            "Prefix is longer than a {:?} hash ({} hex digits)",
+            hash_kind, max_len
+        )
+        .into());
+    }
+    Ok(())
 }
 
 fn commit_line_to_format(line: &str, aggregate_values: &mut CommitValues) -> String {
@@ -79,21 +99,19 @@ fn find_beautiful_git_hash(
     prefix: &str,
     min_minutes: i64,
     max_minutes: i64,
-) -> Result<Option<(String, String)>, Box<dyn Error>> {
-    let allowed_prefix_chars = "0123456789abcdef";
-    if !prefix.chars().all(|c| allowed_prefix_chars.contains(c)) {
-        return Err("Invalid prefix! Only lower case hex digits are allowed".into());
-    }
+    hash_kind: gix_hash::Kind,
+) -> Result<Option<(String, String, String)>, Box<dyn Error>> {
+    validate_prefix(prefix, hash_kind)?;
     let (commit_format, old_values) = commit_to_format(old_commit)?;
 
     let lower_bound = min_minutes * 60;
     let upper_bound = max_minutes * 60;
     let bound = upper_bound - lower_bound;
     let possibilities = (bound + 1) * (bound + 2) / 2;
-    let hash_count = (allowed_prefix_chars.len() as u64).pow(prefix.len() as u32);
-    let probability = possibilities as f64 / hash_count as f64;
+    let hash_count = hash_space_size(prefix.len());
+    let probability = possibilities as f64 / hash_count;
     println!(
-        "Searching for a hash starting with {} (1:{}) in {} commits (probability: {:.2}% <{:.2} times>)",
+        "Searching for a hash starting with {} (1:{:.0}) in {} commits (probability: {:.2}% <{:.2} times>)",
         prefix,
         hash_count,
         possibilities,
@@ -132,7 +150,7 @@ fn find_beautiful_git_hash(
                         "%(committer_date_timestamp)i",
                         &new_values.committer_date_timestamp.to_string(),
                     );
-                if git_commit_hash(&commit).starts_with(prefix) {
+                if git_commit_hash(&commit, hash_kind).starts_with(prefix) {
                     if author_date_offset == 0 && committer_date_offset == 0 {
                         return Some(None);
                     } else {
@@ -144,7 +162,7 @@ fn find_beautiful_git_hash(
                             "{} {}",
                             new_values.author_date_timestamp, new_values.author_date_tz
                         );
-                        return Some(Some((committer_date, author_date)));
+                        return Some(Some((committer_date, author_date, commit)));
                     }
                 }
             }
@@ -158,40 +176,174 @@ fn find_beautiful_git_hash(
     Err("Unable to find beautiful hash!".into())
 }
 
-fn proposed_prefix(previous_commit: &str, number_length: usize) -> String {
-    let output = subprocess_check_output(&format!("git rev-parse {} 2>/dev/null", previous_commit))
+fn find_beautiful_git_hash_signed(
+    old_commit: &str,
+    prefix: &str,
+    signing_key: &signing::SigningKey,
+    max_attempts: u64,
+    hash_kind: gix_hash::Kind,
+) -> Result<Option<String>, Box<dyn Error>> {
+    validate_prefix(prefix, hash_kind)?;
+    let unsigned_commit = signing::strip_gpgsig(old_commit);
+    let hash_count = hash_space_size(prefix.len());
+    println!(
+        "Re-signing up to {} times searching for a hash starting with {} (1:{:.0})",
+        max_attempts, prefix, hash_count
+    );
+
+    let bar = tqdm!(total = max_attempts as usize);
+    let shared_bar = Arc::new(Mutex::new(bar));
+    let found = AtomicBool::new(false);
+
+    let result = (0..max_attempts).into_par_iter().find_map_any(|_| {
+        if found.load(Ordering::Relaxed) {
+            return None;
+        }
+        shared_bar.lock().unwrap().update(1);
+        let armored_sig = signing_key.sign_detached(&unsigned_commit).ok()?;
+        let signed_commit = signing::insert_gpgsig(&unsigned_commit, &armored_sig);
+        if git_commit_hash(&signed_commit, hash_kind).starts_with(prefix) {
+            found.store(true, Ordering::Relaxed);
+            Some(signed_commit)
+        } else {
+            None
+        }
+    });
+
+    Ok(result)
+}
+
+fn proposed_prefix(repo: &gix::Repository, previous_commit: &str, number_length: usize) -> String {
+    let previous_commit_hash = repo::resolve(repo, previous_commit)
+        .map(|id| id.to_string())
         .unwrap_or_default();
-    let previous_commit_hash = output.trim_end();
-    let new_number = previous_commit_hash[..number_length]
-        .parse::<u64>()
+    let new_number = previous_commit_hash
+        .get(..number_length)
+        .and_then(|digits| digits.parse::<u64>().ok())
         .map(|n| n + 1)
         .unwrap_or(1);
     format!("{:0>width$}a", new_number, width = number_length)
 }
 
-fn show_proposal_for_git_head(
+fn show_proposal_for_commit(
+    repo: &gix::Repository,
+    commit_rev: &str,
     prefix: Option<String>,
     min_minutes: i64,
     max_minutes: i64,
+    apply: bool,
 ) -> Result<(), Box<dyn Error>> {
-    let prefix = prefix.unwrap_or_else(|| proposed_prefix("HEAD^", 4));
+    let prefix = prefix.unwrap_or_else(|| proposed_prefix(repo, &format!("{}^", commit_rev), 4));
 
     println!("Searching for a hash starting with {} in the last {} minutes or the next {} minutes", prefix, -min_minutes, max_minutes);
 
-    let old_commit = load_git_commit("HEAD")?;
-    let values = find_beautiful_git_hash(&old_commit, &prefix, min_minutes, max_minutes)?;
+    let commit_id = repo::resolve(repo, commit_rev)?;
+    let old_commit = repo::read_commit(repo, commit_id)?;
+    let hash_kind = repo::object_hash(repo);
+    let values = find_beautiful_git_hash(&old_commit, &prefix, min_minutes, max_minutes, hash_kind)?;
     //let values = find_beautiful_git_hash(&old_commit, &prefix, -900, 600)?;
     //let values = find_beautiful_git_hash(&old_commit, &prefix, -2000, -900)?;
     // let values = find_beautiful_git_hash(&old_commit, &prefix, -4000, -2000)?;
 
-    if let Some((committer_date, author_date)) = values {
-        println!("Proposal:");
-        println!(
-            "GIT_COMMITTER_DATE='{}' git commit --amend -C HEAD --date='{}'",
-            committer_date, author_date
-        );
-    } else {
-        println!("Nothing to do");
+    match values {
+        Some((_, _, new_commit)) if apply => {
+            println!("Rewriting {} and every descendant up to the branch tip...", commit_rev);
+            let new_tip = rewrite::rewrite_descendants(repo, commit_id, &new_commit)?;
+            println!("Done. Branch tip is now {}", new_tip);
+        }
+        Some((committer_date, author_date, _)) if commit_rev == "HEAD" => {
+            println!("Proposal:");
+            println!(
+                "GIT_COMMITTER_DATE='{}' git commit --amend -C HEAD --date='{}'",
+                committer_date, author_date
+            );
+        }
+        Some(_) => {
+            println!(
+                "Would rewrite {} and every descendant up to the branch tip. Pass --apply to perform it.",
+                commit_rev
+            );
+        }
+        None => println!("Nothing to do"),
+    }
+
+    Ok(())
+}
+
+fn show_signed_proposal_for_commit(
+    repo: &gix::Repository,
+    commit_rev: &str,
+    prefix: &str,
+    key: &signing::SigningKey,
+    attempts: u64,
+    apply: bool,
+) -> Result<(), Box<dyn Error>> {
+    println!(
+        "Re-signing {}, searching for a hash starting with {}",
+        commit_rev, prefix
+    );
+
+    let commit_id = repo::resolve(repo, commit_rev)?;
+    let old_commit = repo::read_commit(repo, commit_id)?;
+    let hash_kind = repo::object_hash(repo);
+    match find_beautiful_git_hash_signed(&old_commit, prefix, key, attempts, hash_kind)? {
+        Some(signed_commit) if apply => {
+            println!("Rewriting {} and every descendant up to the branch tip...", commit_rev);
+            let new_tip = rewrite::rewrite_descendants(repo, commit_id, &signed_commit)?;
+            println!("Done. Branch tip is now {}", new_tip);
+        }
+        Some(signed_commit) if commit_rev == "HEAD" => {
+            let new_hash = git_commit_hash(&signed_commit, hash_kind);
+            println!("Proposal (new hash {}):", new_hash);
+            println!("Pipe the object below through `git hash-object -w -t commit --stdin`, then point HEAD (or the branch it's on) at the resulting hash:");
+            print!("{}", signed_commit);
+        }
+        Some(_) => {
+            println!(
+                "Would rewrite {} and every descendant up to the branch tip. Pass --apply to perform it.",
+                commit_rev
+            );
+        }
+        None => println!(
+            "No signature starting with {} found in {} attempts. Try again, raise --attempts, or shorten the prefix.",
+            prefix, attempts
+        ),
+    }
+
+    Ok(())
+}
+
+fn show_padded_proposal_for_commit(
+    repo: &gix::Repository,
+    commit_rev: &str,
+    prefix: &str,
+    pad_bits: u32,
+    apply: bool,
+) -> Result<(), Box<dyn Error>> {
+    let hash_kind = repo::object_hash(repo);
+    if hash_kind != gix_hash::Kind::Sha1 {
+        return Err("--pad-bits currently only supports sha1 repositories".into());
+    }
+
+    let commit_id = repo::resolve(repo, commit_rev)?;
+    let old_commit = repo::read_commit(repo, commit_id)?;
+    match padding::find_beautiful_git_hash_padded(&old_commit, prefix, pad_bits)? {
+        Some(new_commit) if apply => {
+            println!("Rewriting {} and every descendant up to the branch tip...", commit_rev);
+            let new_tip = rewrite::rewrite_descendants(repo, commit_id, &new_commit)?;
+            println!("Done. Branch tip is now {}", new_tip);
+        }
+        Some(new_commit) => {
+            let new_hash = git_commit_hash(&new_commit, hash_kind);
+            println!("Proposal (new hash {}):", new_hash);
+            println!("Pipe the object below through `git hash-object -w -t commit --stdin`, then point {} at the resulting hash (or pass --apply):", commit_rev);
+            print!("{}", new_commit);
+        }
+        None => println!(
+            "No hash starting with {} found among the {} whitespace-padding candidates. Try again, raise --pad-bits, or shorten the prefix.",
+            prefix,
+            1u64 << pad_bits
+        ),
     }
 
     Ok(())
@@ -211,6 +363,41 @@ struct Args {
     /// Maximum number of minutes to add to the commit date
     #[arg(short = 'M', long, default_value_t = 300)]
     max: i32,
+
+    /// Commit to beautify. Defaults to HEAD; any other revision also
+    /// rewrites every descendant up to the current branch's tip
+    #[arg(short, long, default_value = "HEAD")]
+    commit: String,
+
+    /// Re-sign the commit on every attempt instead of perturbing its dates.
+    /// Requires --key. Use this for commits that carry a `gpgsig` header,
+    /// since shifting dates invalidates the signature.
+    #[arg(long)]
+    sign: bool,
+
+    /// Path to an OpenPGP secret key (certificate) file used with --sign
+    #[arg(long)]
+    key: Option<String>,
+
+    /// Key ID or fingerprint of the signing (sub)key to use from --key, if
+    /// it contains more than one
+    #[arg(long = "key-id")]
+    key_id: Option<String>,
+
+    /// Maximum number of re-signing attempts to make in --sign mode
+    #[arg(long, default_value_t = 1_000_000)]
+    attempts: u64,
+
+    /// Perform the rewrite directly instead of only printing a proposal.
+    /// The default is a dry run: nothing is written until --apply is given
+    #[arg(long)]
+    apply: bool,
+
+    /// Search by appending this many bits of trailing whitespace padding
+    /// instead of perturbing dates, removing the date window's probability
+    /// ceiling. Mutually exclusive with --sign; sha1 repositories only
+    #[arg(long = "pad-bits", conflicts_with = "sign")]
+    pad_bits: Option<u32>,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
@@ -218,6 +405,22 @@ fn main() -> Result<(), Box<dyn Error>> {
     if args.min > args.max {
         return Err("min must be smaller than max".into());
     }
-    show_proposal_for_git_head(Some(args.prefix), args.min as i64, args.max as i64)?;
+    let repo = repo::open()?;
+    if args.sign {
+        let key_path = args.key.as_deref().ok_or("--sign requires --key <keyfile>")?;
+        let key = signing::SigningKey::load(key_path, args.key_id.as_deref())?;
+        show_signed_proposal_for_commit(&repo, &args.commit, &args.prefix, &key, args.attempts, args.apply)?;
+    } else if let Some(pad_bits) = args.pad_bits {
+        show_padded_proposal_for_commit(&repo, &args.commit, &args.prefix, pad_bits, args.apply)?;
+    } else {
+        show_proposal_for_commit(
+            &repo,
+            &args.commit,
+            Some(args.prefix),
+            args.min as i64,
+            args.max as i64,
+            args.apply,
+        )?;
+    }
     Ok(())
 }
@@ -0,0 +1,119 @@
+//! Whitespace-padding search mode: instead of perturbing timestamps, append
+//! a fixed-length block of trailing whitespace (space `0x20` = 0, tab
+//! `0x09` = 1) to the commit content and enumerate it as an N-bit counter.
+//! Git tolerates trailing whitespace on the last line of a commit message,
+//! so the commit stays valid, and this decouples the search from the date
+//! window entirely.
+//!
+//! Because the padding sits at the very end and its length never changes,
+//! the `commit <len>\0` header and every complete leading 64-byte SHA-1
+//! block are identical across all attempts. We hash them once into a
+//! `Sha1` state and clone that primed state per attempt, so each guess only
+//! runs the compression function over the trailing partial block(s)
+//! instead of rehashing the whole object.
+
+use sha1::{Digest, Sha1};
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+
+use kdam::{tqdm, BarExt};
+use rayon::prelude::*;
+
+/// Splits `content` into its complete leading 64-byte SHA-1 blocks, primed
+/// into a `Sha1` state, and the remaining bytes that still need hashing.
+/// Cloning the returned state and feeding it only the leftover (plus
+/// whatever comes after `content`) yields the same digest as hashing the
+/// whole thing from scratch, since SHA-1 processes input in fixed 64-byte
+/// blocks and a `Sha1`'s state fully captures its progress between them.
+fn midstate(content: &[u8]) -> (Sha1, &[u8]) {
+    let full_blocks_len = (content.len() / 64) * 64;
+    let (primed_bytes, leftover) = content.split_at(full_blocks_len);
+    let mut base_hasher = Sha1::new();
+    base_hasher.update(primed_bytes);
+    (base_hasher, leftover)
+}
+
+/// Searches `2^padding_bits` whitespace-padding candidates for one whose
+/// SHA-1 hash starts with `prefix`, returning the padded commit content.
+/// Only supports SHA-1 repositories; the padding trick relies on
+/// reproducing SHA-1's own block structure.
+pub fn find_beautiful_git_hash_padded(
+    old_commit: &str,
+    prefix: &str,
+    padding_bits: u32,
+) -> Result<Option<String>, Box<dyn Error>> {
+    crate::validate_prefix(prefix, gix_hash::Kind::Sha1)?;
+    if padding_bits == 0 || padding_bits > 40 {
+        return Err("--pad-bits must be between 1 and 40".into());
+    }
+
+    let final_len = old_commit.len() + padding_bits as usize;
+    let mut fixed_prefix = format!("commit {}\x00", final_len).into_bytes();
+    fixed_prefix.extend_from_slice(old_commit.as_bytes());
+
+    let (base_hasher, leftover) = midstate(&fixed_prefix);
+
+    let attempt_count = 1u64 << padding_bits;
+    let hash_count = crate::hash_space_size(prefix.len());
+    println!(
+        "Searching {} whitespace-padding candidates for a hash starting with {} (1:{:.0})",
+        attempt_count, prefix, hash_count
+    );
+
+    let bar = tqdm!(total = attempt_count as usize);
+    let shared_bar = Arc::new(Mutex::new(bar));
+
+    let result = (0..attempt_count).into_par_iter().find_map_any(|candidate| {
+        let padding: Vec<u8> = (0..padding_bits)
+            .map(|bit| if (candidate >> bit) & 1 == 1 { b'\t' } else { b' ' })
+            .collect();
+
+        let mut hasher = base_hasher.clone();
+        hasher.update(leftover);
+        hasher.update(&padding);
+        let hash = format!("{:x}", hasher.finalize());
+
+        shared_bar.lock().unwrap().update(1);
+        if hash.starts_with(prefix) {
+            Some(format!("{}{}", old_commit, String::from_utf8_lossy(&padding)))
+        } else {
+            None
+        }
+    });
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Feeds the same bytes through the midstate-primed hasher and through a
+    /// single one-shot `Sha1::digest` and checks they agree, for a prefix
+    /// length that doesn't land on a 64-byte block boundary (block-aligned
+    /// lengths would trivially pass even if the split math were off by one).
+    #[test]
+    fn midstate_hash_matches_one_shot_digest_for_a_non_block_aligned_length() {
+        let old_commit = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor test <test@example.com> 1700000000 +0000\ncommitter test <test@example.com> 1700000000 +0000\n\nmessage\n";
+        let padding_bits = 5;
+        let final_len = old_commit.len() + padding_bits as usize;
+        let mut fixed_prefix = format!("commit {}\x00", final_len).into_bytes();
+        fixed_prefix.extend_from_slice(old_commit.as_bytes());
+        assert_ne!(fixed_prefix.len() % 64, 0, "fixture must not be block-aligned");
+
+        let padding: Vec<u8> = b"\t \t\t ".to_vec();
+        assert_eq!(padding.len(), padding_bits as usize);
+
+        let (base_hasher, leftover) = midstate(&fixed_prefix);
+        let mut hasher = base_hasher.clone();
+        hasher.update(leftover);
+        hasher.update(&padding);
+        let midstate_hash = format!("{:x}", hasher.finalize());
+
+        let mut whole = fixed_prefix.clone();
+        whole.extend_from_slice(&padding);
+        let one_shot_hash = format!("{:x}", Sha1::digest(&whole));
+
+        assert_eq!(midstate_hash, one_shot_hash);
+    }
+}
@@ -0,0 +1,195 @@
+//! Repository access through gitoxide (`gix`), replacing the earlier
+//! `sh -c git ...` subprocess layer. This drops the external `git` binary
+//! dependency, makes the tool usable as a library, and lets us read/write
+//! objects directly against the on-disk object database.
+
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::error::Error;
+
+/// Opens the repository that contains the current working directory,
+/// discovering it the same way `git` itself walks upward looking for one.
+pub fn open() -> Result<gix::Repository, Box<dyn Error>> {
+    Ok(gix::discover(".")?)
+}
+
+/// Resolves `rev` (e.g. `HEAD`, `HEAD^`, a branch name, or a hash prefix) to
+/// the object id it names.
+pub fn resolve(repo: &gix::Repository, rev: &str) -> Result<gix::ObjectId, Box<dyn Error>> {
+    Ok(repo.rev_parse_single(rev)?.detach())
+}
+
+/// The hash algorithm this repository's objects use, i.e. `sha1` unless the
+/// repository was created with `--object-format=sha256`.
+pub fn object_hash(repo: &gix::Repository) -> gix_hash::Kind {
+    repo.object_hash()
+}
+
+/// Reads a commit object's content, i.e. exactly the bytes that follow the
+/// `commit <len>\0` header git hashes it under.
+pub fn read_commit(repo: &gix::Repository, id: gix::ObjectId) -> Result<String, Box<dyn Error>> {
+    let object = repo.find_object(id)?;
+    if object.kind != gix::objs::Kind::Commit {
+        return Err(format!("{} is not a commit", id).into());
+    }
+    Ok(String::from_utf8(object.data.to_vec())?)
+}
+
+/// Writes `content` as a new commit object into the object database and
+/// returns its id.
+pub fn write_commit(repo: &gix::Repository, content: &str) -> Result<gix::ObjectId, Box<dyn Error>> {
+    Ok(repo.write_object(gix::objs::Kind::Commit, content.as_bytes())?.detach())
+}
+
+/// Parses the `parent <id>` headers out of a commit's raw content.
+fn commit_parents(repo: &gix::Repository, id: gix::ObjectId) -> Result<Vec<gix::ObjectId>, Box<dyn Error>> {
+    Ok(read_commit(repo, id)?
+        .split('\n')
+        .take_while(|line| !line.is_empty())
+        .filter_map(|line| line.strip_prefix("parent "))
+        .filter_map(|hex| gix::ObjectId::from_hex(hex.as_bytes()).ok())
+        .collect())
+}
+
+/// The set of commits reachable from `tip`, inclusive, walking every parent
+/// all the way back to the root(s) regardless of what else is in scope.
+fn reachable_from(repo: &gix::Repository, tip: gix::ObjectId) -> Result<BTreeSet<gix::ObjectId>, Box<dyn Error>> {
+    let mut seen = BTreeSet::new();
+    let mut queue = VecDeque::from([tip]);
+    while let Some(id) = queue.pop_front() {
+        if !seen.insert(id) {
+            continue;
+        }
+        for parent in commit_parents(repo, id)? {
+            if !seen.contains(&parent) {
+                queue.push_back(parent);
+            }
+        }
+    }
+    Ok(seen)
+}
+
+/// Whether `candidate` is `tip` itself or one of its ancestors.
+pub fn is_ancestor(
+    repo: &gix::Repository,
+    candidate: gix::ObjectId,
+    tip: gix::ObjectId,
+) -> Result<bool, Box<dyn Error>> {
+    if candidate == tip {
+        return Ok(true);
+    }
+    let mut seen = BTreeSet::new();
+    let mut queue = VecDeque::from([tip]);
+    while let Some(id) = queue.pop_front() {
+        if !seen.insert(id) {
+            continue;
+        }
+        for parent in commit_parents(repo, id)? {
+            if parent == candidate {
+                return Ok(true);
+            }
+            queue.push_back(parent);
+        }
+    }
+    Ok(false)
+}
+
+/// Lists the commits strictly between `base` (exclusive) and `tip`
+/// (inclusive) in topological order (every parent emitted before the
+/// children that reference it), matching
+/// `git rev-list --reverse --topo-order base..tip`.
+///
+/// The in-scope set is computed as `reachable(tip) \ reachable(base)`,
+/// mirroring git's own two-dot-range semantics. That full subtraction
+/// matters once history has converged again (a long-lived branch merged
+/// back in): walking from `tip` and simply stopping whenever a path hits
+/// `base` would instead keep going on every *other* path into shared
+/// ancestry, sweeping unrelated pre-existing commits into the rewrite set
+/// even though `base` is reachable from `tip` by a different route.
+///
+/// A plain breadth-first walk is not enough here either: a commit reachable
+/// from `tip` via two paths of different length (the merge-commit case this
+/// is for) gets assigned the order of its *shortest* path, which can place
+/// it before a parent it actually depends on. Kahn's algorithm over the
+/// in-scope subgraph guarantees every parent — including every parent of a
+/// merge commit — is emitted first.
+pub fn descendants(
+    repo: &gix::Repository,
+    base: gix::ObjectId,
+    tip: gix::ObjectId,
+) -> Result<Vec<gix::ObjectId>, Box<dyn Error>> {
+    let base_ancestors = reachable_from(repo, base)?;
+    let in_scope: BTreeSet<gix::ObjectId> = reachable_from(repo, tip)?
+        .difference(&base_ancestors)
+        .copied()
+        .collect();
+
+    let mut parents_in_scope: BTreeMap<gix::ObjectId, Vec<gix::ObjectId>> = BTreeMap::new();
+    let mut children: BTreeMap<gix::ObjectId, Vec<gix::ObjectId>> = BTreeMap::new();
+    for &id in &in_scope {
+        let parents: Vec<gix::ObjectId> = commit_parents(repo, id)?
+            .into_iter()
+            .filter(|p| in_scope.contains(p))
+            .collect();
+        for &parent in &parents {
+            children.entry(parent).or_default().push(id);
+        }
+        parents_in_scope.insert(id, parents);
+    }
+
+    let mut remaining: BTreeMap<gix::ObjectId, usize> = parents_in_scope
+        .iter()
+        .map(|(&id, parents)| (id, parents.len()))
+        .collect();
+    let mut ready: VecDeque<gix::ObjectId> = remaining
+        .iter()
+        .filter(|&(_, &count)| count == 0)
+        .map(|(&id, _)| id)
+        .collect();
+
+    let mut order = Vec::with_capacity(in_scope.len());
+    while let Some(id) = ready.pop_front() {
+        order.push(id);
+        for &child in children.get(&id).map(Vec::as_slice).unwrap_or_default() {
+            let left = remaining.get_mut(&child).expect("child was counted when building `remaining`");
+            *left -= 1;
+            if *left == 0 {
+                ready.push_back(child);
+            }
+        }
+    }
+
+    if order.len() != in_scope.len() {
+        return Err("commit graph between base and tip is not a DAG (cycle detected)".into());
+    }
+    Ok(order)
+}
+
+/// The full ref name `HEAD` currently points at (e.g. `refs/heads/main`),
+/// so a rewritten chain's new tip gets written back to the right branch.
+pub fn current_branch_ref(repo: &gix::Repository) -> Result<String, Box<dyn Error>> {
+    let head = repo.head_ref()?.ok_or("HEAD does not currently point at a branch")?;
+    Ok(head.name().as_bstr().to_string())
+}
+
+/// Moves `reference` to point at `new_tip`.
+pub fn update_ref(
+    repo: &gix::Repository,
+    reference: &str,
+    new_tip: gix::ObjectId,
+) -> Result<(), Box<dyn Error>> {
+    use gix::refs::transaction::{Change, LogChange, PreviousValue, RefEdit};
+
+    repo.edit_reference(RefEdit {
+        change: Change::Update {
+            log: LogChange {
+                message: "git-hash-chooser: rewrite descendants".into(),
+                ..Default::default()
+            },
+            expected: PreviousValue::Any,
+            new: gix::refs::Target::Peeled(new_tip),
+        },
+        name: reference.try_into()?,
+        deref: true,
+    })?;
+    Ok(())
+}
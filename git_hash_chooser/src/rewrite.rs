@@ -0,0 +1,160 @@
+//! Rewriting a commit buried in history requires more than replacing a
+//! single object: every descendant's `parent` header has to be redirected
+//! at the rewritten ancestor and re-hashed in turn, like a scoped
+//! `filter-branch`. This module walks that chain and moves the branch ref
+//! to the new tip once it's done.
+
+use crate::repo;
+use std::error::Error;
+
+/// Rewrites every `parent <old_id>` header in `commit` to `parent
+/// <new_id>`, leaving merge commits' other parents untouched.
+fn redirect_parent(commit: &str, old_id: gix::ObjectId, new_id: gix::ObjectId) -> String {
+    let old_line = format!("parent {}", old_id);
+    let new_line = format!("parent {}", new_id);
+    commit
+        .split('\n')
+        .map(|line| if line == old_line { new_line.as_str() } else { line })
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+/// Rewrites `target` to `new_target_content`, then every descendant of
+/// `target` up to the current branch's tip, redirecting each child's
+/// `parent` header at its freshly rewritten ancestor before re-hashing it.
+/// Finally moves the branch ref to the new tip. Returns the new tip id.
+pub fn rewrite_descendants(
+    repo: &gix::Repository,
+    target: gix::ObjectId,
+    new_target_content: &str,
+) -> Result<gix::ObjectId, Box<dyn Error>> {
+    let branch_ref = repo::current_branch_ref(repo)?;
+    let old_tip = repo::resolve(repo, &branch_ref)?;
+
+    if !repo::is_ancestor(repo, target, old_tip)? {
+        return Err(format!(
+            "{} is not an ancestor of {} ({}), so there are no descendants to rewrite",
+            target, branch_ref, old_tip
+        )
+        .into());
+    }
+
+    let mut tip_id = repo::write_commit(repo, new_target_content)?;
+    let mut old_to_new = vec![(target, tip_id)];
+
+    for old_id in repo::descendants(repo, target, old_tip)? {
+        let mut commit = repo::read_commit(repo, old_id)?;
+        for &(old_parent, new_parent) in &old_to_new {
+            commit = redirect_parent(&commit, old_parent, new_parent);
+        }
+        let new_id = repo::write_commit(repo, &commit)?;
+        old_to_new.push((old_id, new_id));
+        tip_id = new_id;
+    }
+
+    repo::update_ref(repo, &branch_ref, tip_id)?;
+    Ok(tip_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    const EMPTY_TREE: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+    fn temp_repo() -> gix::Repository {
+        let dir = std::env::temp_dir().join(format!(
+            "git-hash-chooser-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        gix::init(&dir).expect("init temp repo")
+    }
+
+    fn make_commit(repo: &gix::Repository, message: &str, parents: &[gix::ObjectId]) -> gix::ObjectId {
+        let parent_lines: String = parents.iter().map(|p| format!("parent {}\n", p)).collect();
+        let content = format!(
+            "tree {}\n{}author test <test@example.com> 1700000000 +0000\ncommitter test <test@example.com> 1700000000 +0000\n\n{}\n",
+            EMPTY_TREE, parent_lines, message
+        );
+        repo::write_commit(repo, &content).expect("write commit")
+    }
+
+    /// `merge`'s two parents are `a` and `b`, but `b`'s own parent is `a` —
+    /// so `a` is reachable from `merge` both directly (distance 1) and via
+    /// `b` (distance 2). A breadth-first walk from `merge` would mark `a`
+    /// as seen on the short path and never revisit it through `b`, so the
+    /// reversed walk emits `b` before `a` even though `b`'s `parent` header
+    /// points at `a`. `rewrite_descendants` must still redirect `b`'s
+    /// parent to the rewritten `a`, which only happens if `a` is rewritten
+    /// first.
+    #[test]
+    fn rewrite_descendants_handles_merge_parents_at_different_depths() {
+        let repo = temp_repo();
+
+        let root = make_commit(&repo, "root", &[]);
+        let a = make_commit(&repo, "a", &[root]);
+        let b = make_commit(&repo, "b", &[a]);
+        let merge = make_commit(&repo, "merge", &[a, b]);
+        repo::update_ref(&repo, "refs/heads/main", merge).expect("point main at merge");
+
+        let new_root_content = repo::read_commit(&repo, root)
+            .unwrap()
+            .replace("root", "root (rewritten)");
+        let new_tip = rewrite_descendants(&repo, root, &new_root_content).expect("rewrite");
+
+        let old_ids = [root, a, b, merge];
+        let mut frontier = vec![new_tip];
+        let mut seen = std::collections::BTreeSet::new();
+        let mut visited = 0;
+        while let Some(id) = frontier.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            visited += 1;
+            let content = repo::read_commit(&repo, id).expect("read rewritten commit");
+            for parent in content
+                .lines()
+                .take_while(|l| !l.is_empty())
+                .filter_map(|l| l.strip_prefix("parent "))
+            {
+                let parent_id = gix::ObjectId::from_hex(parent.as_bytes()).unwrap();
+                assert!(
+                    !old_ids.contains(&parent_id),
+                    "rewritten chain still references an un-rewritten commit id"
+                );
+                frontier.push(parent_id);
+            }
+        }
+        assert_eq!(visited, 4, "expected to walk root, a, b and the merge commit");
+    }
+
+    /// `target`'s sibling `w` branches off the same `root` and later merges
+    /// back in above `target`, so `root` is reachable from `merge` via `w`
+    /// without ever passing through `target`. The in-scope set still must
+    /// exclude `root`, because `root` is an ancestor of `target` via the
+    /// *other* path — true two-dot-range semantics (`reachable(tip) \
+    /// reachable(base)`), not "stop expanding a path once you hit `target`
+    /// on it". A BFS that only special-cased the literal `target` node would
+    /// walk straight past it along the `w` branch and pull `root` (and
+    /// anything further back) into the rewrite set even though it has
+    /// nothing to do with `target`'s descendants.
+    #[test]
+    fn descendants_excludes_bases_ancestors_reached_via_a_converged_sibling_branch() {
+        let repo = temp_repo();
+
+        let root = make_commit(&repo, "root", &[]);
+        let target = make_commit(&repo, "target", &[root]);
+        let w = make_commit(&repo, "w", &[root]);
+        let merge = make_commit(&repo, "merge", &[target, w]);
+
+        let order = repo::descendants(&repo, target, merge).expect("compute descendants");
+
+        assert!(!order.contains(&root), "root is an ancestor of target and must not be in scope");
+        assert_eq!(order.len(), 2, "expected exactly w and merge in scope, got {:?}", order);
+        assert_eq!(order.last(), Some(&merge), "merge must be emitted last");
+        assert!(order.contains(&w));
+    }
+}
@@ -0,0 +1,144 @@
+//! Support for the `--sign` search mode: instead of perturbing commit
+//! timestamps, we keep the dates fixed and re-sign the commit on every
+//! attempt. An OpenPGP signature embeds a creation time and random salt, so
+//! signing identical content twice yields two different signatures (and
+//! therefore two different hashes), which is enough to walk the hash space.
+
+use openpgp::armor::Kind;
+use openpgp::cert::Cert;
+use openpgp::parse::Parse;
+use openpgp::policy::StandardPolicy;
+use openpgp::serialize::stream::{Armorer, Message, Signer};
+use sequoia_openpgp as openpgp;
+use std::error::Error;
+use std::io::Write;
+
+/// A signing (sub)key selected once out of a certificate and held ready to
+/// clone, so that every attempt in the search loop only pays for the
+/// signature itself, not for re-walking the certificate's key list and
+/// re-selecting a key.
+pub struct SigningKey {
+    keypair: openpgp::crypto::KeyPair,
+}
+
+impl SigningKey {
+    /// Loads a secret key (certificate) from `path` and selects the signing
+    /// (sub)key to use. If the certificate carries more than one
+    /// signing-capable key, `key_id` selects which one by key ID or
+    /// fingerprint (case-insensitive hex).
+    pub fn load(path: &str, key_id: Option<&str>) -> Result<Self, Box<dyn Error>> {
+        let cert = Cert::from_file(path)?;
+        let keypair = Self::select_keypair(&cert, key_id)?;
+        Ok(SigningKey { keypair })
+    }
+
+    fn select_keypair(cert: &Cert, key_id: Option<&str>) -> Result<openpgp::crypto::KeyPair, Box<dyn Error>> {
+        let policy = StandardPolicy::new();
+        let mut candidates = cert.keys().with_policy(&policy, None).for_signing().secret();
+        let key = match key_id {
+            Some(id) => candidates
+                .find(|ka| {
+                    ka.key().keyid().to_hex().eq_ignore_ascii_case(id)
+                        || ka.key().fingerprint().to_hex().eq_ignore_ascii_case(id)
+                })
+                .ok_or_else(|| format!("no signing (sub)key with id {} in the given certificate", id))?,
+            None => candidates
+                .next()
+                .ok_or("certificate has no usable signing key")?,
+        };
+        Ok(key.key().clone().into_keypair()?)
+    }
+
+    /// Produces a fresh ASCII-armored detached signature over `content`.
+    /// Calling this twice with the same `content` yields two different
+    /// signatures, since OpenPGP signatures include a creation time and salt.
+    pub fn sign_detached(&self, content: &str) -> Result<String, Box<dyn Error>> {
+        let keypair = self.keypair.clone();
+        let mut armored = Vec::new();
+        {
+            let message = Message::new(&mut armored);
+            let message = Armorer::new(message).kind(Kind::Signature).build()?;
+            let mut signer = Signer::new(message, keypair).detached().build()?;
+            signer.write_all(content.as_bytes())?;
+            signer.finalize()?;
+        }
+        Ok(String::from_utf8(armored)?)
+    }
+}
+
+/// Strips any existing `gpgsig` header (and its folded continuation lines)
+/// from a raw commit object, returning the content an unsigned commit would
+/// hash. This is the content that must be re-signed on every attempt.
+pub fn strip_gpgsig(commit: &str) -> String {
+    let mut lines = commit.split('\n').peekable();
+    let mut out = Vec::new();
+    while let Some(line) = lines.next() {
+        if line.starts_with("gpgsig ") {
+            while matches!(lines.peek(), Some(next) if next.starts_with(' ')) {
+                lines.next();
+            }
+            continue;
+        }
+        out.push(line);
+    }
+    out.join("\n")
+}
+
+/// Inserts `armored_sig` into `commit` (which must not already contain a
+/// `gpgsig` header) as a `gpgsig` header placed right before the blank line
+/// that separates the headers from the message, folding continuation lines
+/// with a single leading space exactly as git does.
+pub fn insert_gpgsig(commit: &str, armored_sig: &str) -> String {
+    let header = armored_sig
+        .trim_end_matches('\n')
+        .split('\n')
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 {
+                format!("gpgsig {}", line)
+            } else {
+                format!(" {}", line)
+            }
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let mut lines: Vec<&str> = commit.split('\n').collect();
+    let insert_at = lines.iter().position(|l| l.is_empty()).unwrap_or(lines.len());
+    lines.insert(insert_at, &header);
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNSIGNED: &str = "tree 4b825dc642cb6eb9a060e54bf8d69288fbee4904\nauthor test <test@example.com> 1700000000 +0000\ncommitter test <test@example.com> 1700000000 +0000\n\nmessage\n";
+
+    const ARMORED_SIG: &str = "-----BEGIN PGP SIGNATURE-----\n\niQEzBAABCAAdFiEE...\n=abcd\n-----END PGP SIGNATURE-----";
+
+    #[test]
+    fn insert_then_strip_round_trips_to_the_original_commit() {
+        let signed = insert_gpgsig(UNSIGNED, ARMORED_SIG);
+        assert_eq!(strip_gpgsig(&signed), UNSIGNED);
+    }
+
+    #[test]
+    fn insert_gpgsig_folds_continuation_lines_with_a_single_leading_space() {
+        let signed = insert_gpgsig(UNSIGNED, ARMORED_SIG);
+        for line in ARMORED_SIG.split('\n').skip(1) {
+            assert!(
+                signed.contains(&format!("\n {}\n", line)) || signed.contains(&format!("\n {}", line)),
+                "continuation line {:?} not folded with a single leading space",
+                line
+            );
+        }
+        assert!(signed.starts_with("tree "));
+        assert!(signed.contains("\ngpgsig -----BEGIN PGP SIGNATURE-----\n"));
+    }
+
+    #[test]
+    fn strip_gpgsig_is_a_no_op_on_a_commit_without_one() {
+        assert_eq!(strip_gpgsig(UNSIGNED), UNSIGNED);
+    }
+}